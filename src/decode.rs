@@ -0,0 +1,84 @@
+use crate::{single_byte, Result};
+use csv::{ReaderBuilder, StringRecord};
+use serde_json::{Map, Number, Value};
+use std::io::{BufRead, Write};
+
+pub struct DecodeArgs<'a> {
+    pub separator: &'a str,
+    pub quote: u8,
+    pub escape: Option<u8>,
+    pub has_headers: bool,
+    pub jsonl: bool,
+    pub types: bool,
+}
+
+/// Parses a CSV cell into a JSON value when `--types` is set: empty becomes
+/// `null`, `true`/`false` become booleans, and anything that parses cleanly
+/// as a number becomes one. Everything else stays a string.
+fn parse_cell(field: &str, types: bool) -> Value {
+    if !types {
+        return Value::String(field.to_string());
+    }
+    match field {
+        "" => Value::Null,
+        "true" => Value::Bool(true),
+        "false" => Value::Bool(false),
+        s => {
+            if let Ok(n) = s.parse::<i64>() {
+                Value::Number(Number::from(n))
+            } else if let Ok(f) = s.parse::<f64>() {
+                Number::from_f64(f).map(Value::Number).unwrap_or_else(|| Value::String(s.to_string()))
+            } else {
+                Value::String(s.to_string())
+            }
+        }
+    }
+}
+
+fn record_to_value(record: &StringRecord, headers: Option<&StringRecord>, types: bool) -> Value {
+    match headers {
+        Some(headers) => {
+            let mut map = Map::new();
+            for (i, field) in record.iter().enumerate() {
+                let key = headers.get(i).map(String::from).unwrap_or_else(|| format!("col{}", i));
+                map.insert(key, parse_cell(field, types));
+            }
+            Value::Object(map)
+        }
+        None => Value::Array(record.iter().map(|field| parse_cell(field, types)).collect()),
+    }
+}
+
+/// Reads delimited input and emits JSON: an array of objects keyed by the
+/// header row by default, or newline-delimited JSON with `--jsonl`. Without
+/// headers, each record becomes a JSON array instead.
+pub fn run(args: DecodeArgs, in_stream: impl BufRead, mut out_stream: impl Write) -> Result<()> {
+    let delimiter = single_byte(args.separator, "sep")?;
+    let mut builder = ReaderBuilder::new();
+    builder.delimiter(delimiter).quote(args.quote).has_headers(args.has_headers);
+    if let Some(escape) = args.escape {
+        builder.escape(Some(escape));
+    }
+    let mut reader = builder.from_reader(in_stream);
+    let headers = if args.has_headers {
+        Some(reader.headers()?.clone())
+    } else {
+        None
+    };
+
+    if args.jsonl {
+        for result in reader.records() {
+            let value = record_to_value(&result?, headers.as_ref(), args.types);
+            serde_json::to_writer(&mut out_stream, &value)?;
+            writeln!(out_stream)?;
+        }
+    } else {
+        let mut values = Vec::new();
+        for result in reader.records() {
+            values.push(record_to_value(&result?, headers.as_ref(), args.types));
+        }
+        serde_json::to_writer(&mut out_stream, &Value::Array(values))?;
+        writeln!(out_stream)?;
+    }
+    Ok(())
+}
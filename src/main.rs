@@ -1,131 +1,391 @@
 #[macro_use]
 extern crate failure;
 
+mod decode;
+mod select;
+
 use atty::Stream;
 use clap::{App, Arg, ArgMatches};
+use csv::{QuoteStyle, Terminator, Writer, WriterBuilder};
 use failure::Error;
-use serde_json::{self, Value, Deserializer};
+use serde_json::{self, Map, Number, Value, Deserializer};
 use std::fs::File;
+use std::collections::HashSet;
 use std::io::{self, BufRead, BufWriter, BufReader, Write};
 use std::iter::Iterator;
 use std::path::Path;
 
-type Result<T> = std::result::Result<T, Error>;
+pub(crate) type Result<T> = std::result::Result<T, Error>;
 
 struct JCArgs<'a> {
     columns: Vec<String>,
     separator: &'a str,
     show_headers: bool,
-    raw: bool,
     no_root: bool,
+    quote_style: QuoteStyle,
+    quote: u8,
+    terminator: Terminator,
+    escape: Option<u8>,
+    flatten: bool,
+    flatten_arrays_join: bool,
+    flatten_join_sep: &'a str,
+    null_repr: &'a str,
+    true_repr: &'a str,
+    false_repr: &'a str,
+    float_fmt: Option<usize>,
+    rdr_buf: usize,
+    wtr_buf: usize,
     in_file: Option<&'a Path>,
     out_file: Option<&'a Path>,
 }
 
+pub(crate) fn single_byte(value: &str, flag: &str) -> Result<u8> {
+    if value.len() == 1 {
+        Ok(value.as_bytes()[0])
+    } else {
+        Err(format_err!("--{} expects a single byte, got {:?}", flag, value))
+    }
+}
+
+fn parse_quote_style(value: &str) -> Result<QuoteStyle> {
+    match value {
+        "always" => Ok(QuoteStyle::Always),
+        "necessary" => Ok(QuoteStyle::Necessary),
+        "non-numeric" => Ok(QuoteStyle::NonNumeric),
+        "never" => Ok(QuoteStyle::Never),
+        e => Err(format_err!("invalid --quote-style: {}", e)),
+    }
+}
+
+fn parse_terminator(value: &str) -> Result<Terminator> {
+    match value {
+        "lf" => Ok(Terminator::Any(b'\n')),
+        "crlf" => Ok(Terminator::CRLF),
+        e => Err(format_err!("invalid --terminator: {}", e)),
+    }
+}
+
 impl JCArgs<'_> {
-    fn from_matches<'a>(matches: &'a ArgMatches) -> JCArgs<'a> {
-        JCArgs {
-            columns: matches.values_of_lossy("COLUMNS").unwrap(),
-            raw: matches.is_present("RAW"),
+    fn from_matches<'a>(matches: &'a ArgMatches) -> Result<JCArgs<'a>> {
+        let raw = matches.is_present("RAW");
+        let quote_style = match matches.value_of("QUOTE-STYLE") {
+            Some(s) => parse_quote_style(s)?,
+            None if raw => QuoteStyle::Never,
+            None => QuoteStyle::Necessary,
+        };
+        Ok(JCArgs {
+            columns: matches.values_of_lossy("COLUMNS").unwrap_or_default(),
             separator: matches.value_of("SEP").unwrap(),
             show_headers: !matches.is_present("NO-HEADERS"),
             no_root: matches.is_present("NO-ROOT"),
+            quote_style,
+            quote: single_byte(matches.value_of("QUOTE").unwrap(), "quote")?,
+            terminator: parse_terminator(matches.value_of("TERMINATOR").unwrap())?,
+            escape: matches.value_of("ESCAPE").map(|s| single_byte(s, "escape")).transpose()?,
+            flatten: matches.is_present("FLATTEN"),
+            flatten_arrays_join: matches.value_of("FLATTEN-ARRAYS") == Some("join"),
+            flatten_join_sep: matches.value_of("FLATTEN-JOIN-SEP").unwrap(),
+            null_repr: matches.value_of("NULL").unwrap(),
+            true_repr: matches.value_of("TRUE").unwrap(),
+            false_repr: matches.value_of("FALSE").unwrap(),
+            float_fmt: matches.value_of("FLOAT-FMT").map(|s| {
+                s.parse().map_err(|_| format_err!("--float-fmt expects a decimal precision, got {:?}", s))
+            }).transpose()?,
+            rdr_buf: matches.value_of("RDR-BUF").unwrap().parse()
+                .map_err(|_| format_err!("--rdr-buf expects a byte count"))?,
+            wtr_buf: matches.value_of("WTR-BUF").unwrap().parse()
+                .map_err(|_| format_err!("--wtr-buf expects a byte count"))?,
             in_file: matches.value_of_os("INPUT").map(|s| Path::new(s)),
             out_file: matches.value_of("OUTPUT").map(|s| Path::new(s)),
-        }
+        })
     }
 
     fn input_or<'a>(&self, stdin: &'a io::Stdin) -> Result<Box<BufRead + 'a>> {
         Ok(if let Some(f) = self.in_file {
-            Box::new(BufReader::new(File::open(f)?))
+            Box::new(BufReader::with_capacity(self.rdr_buf, File::open(f)?))
         } else {
-            Box::new(stdin.lock())
+            Box::new(BufReader::with_capacity(self.rdr_buf, stdin.lock()))
         })
     }
 
     fn output_or<'a>(&self, stdout: &'a io::Stdout) -> Result<Box<Write + 'a>> {
         Ok(if let Some(f) = self.out_file {
-            Box::new(BufWriter::new(File::create(f)?))
+            Box::new(BufWriter::with_capacity(self.wtr_buf, File::create(f)?))
         } else if atty::is(Stream::Stdout) {
             Box::new(stdout.lock())
         } else {
-            Box::new(BufWriter::new(stdout.lock()))
+            Box::new(BufWriter::with_capacity(self.wtr_buf, stdout.lock()))
         })
     }
+
+    fn writer<W: Write>(&self, out_stream: W) -> Result<Writer<W>> {
+        let delimiter = single_byte(self.separator, "sep")?;
+        let mut builder = WriterBuilder::new();
+        builder
+            .delimiter(delimiter)
+            .quote_style(self.quote_style)
+            .quote(self.quote)
+            .terminator(self.terminator);
+        if let Some(escape) = self.escape {
+            builder.double_quote(false).escape(escape);
+        }
+        Ok(builder.from_writer(out_stream))
+    }
 }
 
-fn print_line(element: &Value, args: &JCArgs, out_stream: &mut Write) -> Result<()> {
-    let last_column = args.columns.len() - 1;
-    match element {
-        object @ Value::Object(_) => {
-            for (i, col) in args.columns.iter().enumerate() {
-                match &object[col] {
-                    Value::String(s) => {
-                        if args.raw {
-                            out_stream.write(s.as_bytes())?;
-                        } else {
-                            write!(out_stream, "\"{}\"", s.replace("\"", "\"\""))?;
+enum PathSegment {
+    Key(String),
+    Index(usize),
+}
+
+/// Splits a column path such as `items[0].sku` into `Key`/`Index` segments.
+fn path_segments(path: &str) -> Vec<PathSegment> {
+    let mut segments = Vec::new();
+    for part in path.split('.') {
+        let mut rest = part;
+        match rest.find('[') {
+            Some(bracket) => {
+                if bracket > 0 {
+                    segments.push(PathSegment::Key(rest[..bracket].to_string()));
+                }
+                rest = &rest[bracket..];
+                while rest.starts_with('[') {
+                    match rest.find(']') {
+                        Some(end) => {
+                            if let Ok(index) = rest[1..end].parse::<usize>() {
+                                segments.push(PathSegment::Index(index));
+                            }
+                            rest = &rest[end + 1..];
                         }
+                        None => break,
                     }
-                    Value::Bool(b) => {
-                        write!(out_stream, "{}", b)?;
-                    }
-                    Value::Number(n) => {
-                        write!(out_stream, "{}", n)?;
-                    }
-                    Value::Null => {}
-                    e => return Err(format_err!("invalid column: {}", e))
-                }
-                if i != last_column {
-                    write!(out_stream, "{}", args.separator)?;
                 }
             }
-            write!(out_stream, "\n")?;
+            None => segments.push(PathSegment::Key(rest.to_string())),
         }
-        e => return Err(format_err!("invalid json object: {}", e))
+    }
+    segments
+}
+
+/// Resolves a column against a JSON value, returning `None` for any missing
+/// segment rather than erroring. Columns starting with `/` are treated as
+/// RFC 6901 JSON Pointers; everything else is a dotted path with optional
+/// `[index]` array access.
+fn resolve_column<'v>(value: &'v Value, column: &str) -> Option<&'v Value> {
+    if column.starts_with('/') {
+        return value.pointer(column);
+    }
+    let mut current = value;
+    for segment in path_segments(column) {
+        current = match segment {
+            PathSegment::Key(key) => current.as_object()?.get(&key)?,
+            PathSegment::Index(index) => current.as_array()?.get(index)?,
+        };
+    }
+    Some(current)
+}
+
+fn render_bool(value: bool, args: &JCArgs) -> String {
+    if value { args.true_repr.to_string() } else { args.false_repr.to_string() }
+}
+
+/// Renders a JSON number, applying `--float-fmt` decimal precision when set
+/// and the value was parsed as a float. Integers (including ones too large
+/// to round-trip through `f64`) are always printed as-is, since routing them
+/// through `f64` would silently corrupt them.
+fn render_number(n: &Number, args: &JCArgs) -> String {
+    match (args.float_fmt, n.is_f64()) {
+        (Some(precision), true) => format!("{:.*}", precision, n.as_f64().unwrap()),
+        _ => n.to_string(),
+    }
+}
+
+fn print_line(element: &Value, args: &JCArgs, writer: &mut Writer<impl Write>) -> Result<()> {
+    match element {
+        object @ Value::Object(_) => {
+            let record: Vec<String> = args.columns.iter().map(|col| {
+                match resolve_column(object, col) {
+                    None | Some(Value::Null) => Ok(args.null_repr.to_string()),
+                    Some(Value::String(s)) => Ok(s.clone()),
+                    Some(Value::Bool(b)) => Ok(render_bool(*b, args)),
+                    Some(Value::Number(n)) => Ok(render_number(n, args)),
+                    Some(e) => Err(format_err!("invalid column: {}", e)),
+                }
+            }).collect::<Result<_>>()?;
+            writer.write_record(&record)?;
+        }
+        e => return Err(format_err!("invalid json object: {}", e)),
     }
     Ok(())
 }
 
-fn print_header(args: &JCArgs, out_stream: &mut Write) -> Result<()> {
+fn print_header(args: &JCArgs, writer: &mut Writer<impl Write>) -> Result<()> {
     if args.show_headers {
-        let last_column = args.columns.len() - 1;
-        for (i, col) in args.columns.iter().enumerate() {
-            out_stream.write(col.as_bytes())?;
-            if i != last_column {
-                out_stream.write(args.separator.as_bytes())?;
+        writer.write_record(&args.columns)?;
+    }
+    Ok(())
+}
+
+/// Derives a column list from the union of object keys across all records,
+/// in first-seen order, for use when `--columns` is not given.
+fn infer_columns(elements: &[Value]) -> Vec<String> {
+    let mut columns = Vec::new();
+    let mut seen = HashSet::new();
+    for element in elements {
+        if let Value::Object(map) = element {
+            for key in map.keys() {
+                if seen.insert(key.clone()) {
+                    columns.push(key.clone());
+                }
             }
         }
-        write!(out_stream, "\n")?;
     }
-    Ok(())
+    columns
+}
+
+fn scalar_to_string(value: &Value, args: &JCArgs) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Bool(b) => render_bool(*b, args),
+        Value::Number(n) => render_number(n, args),
+        _ => args.null_repr.to_string(),
+    }
+}
+
+/// Recursively walks a JSON value, inserting one entry per leaf into `out`
+/// keyed by its dotted/bracketed path (`meta.tags[2]`, `geo.lat`). A scalar
+/// array is collapsed into a single joined-string cell when `join_sep` is
+/// `Some`; otherwise arrays expand positionally like nested objects. Leaf
+/// scalars are kept as `Value`s (rendered later by `print_flat_line`), but a
+/// joined array cell is rendered through `--null`/`--true`/`--false`/
+/// `--float-fmt` up front since it collapses into a single string here.
+fn flatten_value(value: &Value, prefix: &str, join_sep: Option<&str>, args: &JCArgs, out: &mut Map<String, Value>) {
+    match value {
+        Value::Object(map) => {
+            for (key, v) in map {
+                let path = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+                flatten_value(v, &path, join_sep, args, out);
+            }
+        }
+        Value::Array(items) => {
+            let all_scalar = items.iter().all(|v| !v.is_object() && !v.is_array());
+            if let Some(sep) = join_sep {
+                if all_scalar {
+                    let joined = items.iter().map(|v| scalar_to_string(v, args)).collect::<Vec<_>>().join(sep);
+                    out.insert(prefix.to_string(), Value::String(joined));
+                    return;
+                }
+            }
+            for (i, v) in items.iter().enumerate() {
+                flatten_value(v, &format!("{}[{}]", prefix, i), join_sep, args, out);
+            }
+        }
+        scalar => {
+            out.insert(prefix.to_string(), scalar.clone());
+        }
+    }
 }
 
+/// Like `infer_columns`, but derives the header from the union of leaf paths
+/// produced by `flatten_value` instead of top-level object keys.
+fn infer_flat_columns(flattened: &[Map<String, Value>]) -> Vec<String> {
+    let mut columns = Vec::new();
+    let mut seen = HashSet::new();
+    for flat in flattened {
+        for key in flat.keys() {
+            if seen.insert(key.clone()) {
+                columns.push(key.clone());
+            }
+        }
+    }
+    columns
+}
 
-fn run(args: JCArgs) -> Result<()> {
+fn print_flat_line(flat: &Map<String, Value>, args: &JCArgs, writer: &mut Writer<impl Write>) -> Result<()> {
+    let record: Vec<String> = args.columns.iter().map(|col| {
+        match flat.get(col) {
+            None | Some(Value::Null) => Ok(args.null_repr.to_string()),
+            Some(Value::String(s)) => Ok(s.clone()),
+            Some(Value::Bool(b)) => Ok(render_bool(*b, args)),
+            Some(Value::Number(n)) => Ok(render_number(n, args)),
+            Some(e) => Err(format_err!("invalid column: {}", e)),
+        }
+    }).collect::<Result<_>>()?;
+    writer.write_record(&record)?;
+    Ok(())
+}
+
+fn run(mut args: JCArgs) -> Result<()> {
     let stdin = io::stdin();
     let stdout = io::stdout();
     let in_stream = args.input_or(&stdin)?;
-    let mut out_stream = args.output_or(&stdout)?;
-
+    let out_stream = args.output_or(&stdout)?;
+    let mut writer = args.writer(out_stream)?;
 
-    if args.no_root {
-        let elements = Deserializer::from_reader(in_stream).into_iter::<Value>();
-        print_header(&args, &mut out_stream)?;
-        for result in elements {
+    // When `--columns` is already a fully-resolved list of literal names/paths
+    // (no inference, no index/range/regex selector that needs the full key
+    // universe), `--no-root` can stay true streaming instead of buffering the
+    // whole NDJSON input into memory.
+    let needs_universe = args.columns.is_empty() || select::needs_universe(&args.columns);
+    if args.no_root && !needs_universe {
+        let join_sep = if args.flatten_arrays_join { Some(args.flatten_join_sep) } else { None };
+        print_header(&args, &mut writer)?;
+        for result in Deserializer::from_reader(in_stream).into_iter::<Value>() {
             let value = result?;
-            print_line(&value, &args, &mut out_stream)?;
+            if args.flatten {
+                let mut flat = Map::new();
+                flatten_value(&value, "", join_sep, &args, &mut flat);
+                print_flat_line(&flat, &args, &mut writer)?;
+            } else {
+                print_line(&value, &args, &mut writer)?;
+            }
         }
-        Ok(())
+        writer.flush()?;
+        return Ok(());
+    }
+
+    let elements: Vec<Value> = if args.no_root {
+        Deserializer::from_reader(in_stream)
+            .into_iter::<Value>()
+            .collect::<serde_json::Result<Vec<Value>>>()?
     } else {
         match serde_json::from_reader(in_stream)? {
-            Value::Array(elements) => {
-                print_header(&args, &mut out_stream)?;
-                elements.iter().try_for_each(|e| print_line(e, &args, &mut out_stream))
-            }
-            _ => Err(format_err!("root object is not an array")),
+            Value::Array(elements) => elements,
+            _ => return Err(format_err!("root object is not an array")),
+        }
+    };
+
+    if args.flatten {
+        let join_sep = if args.flatten_arrays_join { Some(args.flatten_join_sep) } else { None };
+        let flattened: Vec<Map<String, Value>> = elements.iter().map(|e| {
+            let mut flat = Map::new();
+            flatten_value(e, "", join_sep, &args, &mut flat);
+            flat
+        }).collect();
+        let universe = infer_flat_columns(&flattened);
+        args.columns = if args.columns.is_empty() {
+            universe
+        } else {
+            select::resolve_selection(&args.columns, &universe)?
+        };
+        print_header(&args, &mut writer)?;
+        for flat in &flattened {
+            print_flat_line(flat, &args, &mut writer)?;
+        }
+    } else {
+        let universe = infer_columns(&elements);
+        args.columns = if args.columns.is_empty() {
+            universe
+        } else {
+            select::resolve_selection(&args.columns, &universe)?
+        };
+        print_header(&args, &mut writer)?;
+        for element in &elements {
+            print_line(element, &args, &mut writer)?;
         }
     }
+    writer.flush()?;
+    Ok(())
 }
 
 fn main() -> Result<()> {
@@ -161,10 +421,108 @@ fn main() -> Result<()> {
         .arg(Arg::with_name("COLUMNS")
             .short("c")
             .long("columns")
-            .required(true)
             .use_delimiter(true)
-            .help("Columns to output"))
+            .help("Columns to output: names/paths, 1-based indices or ranges (2-5, 3-), /regex/ on \
+                   names, !-prefixed to exclude — resolved against keys inferred from the input; \
+                   default is all inferred columns"))
+        .arg(Arg::with_name("QUOTE-STYLE")
+            .long("quote-style")
+            .takes_value(true)
+            .possible_values(&["always", "necessary", "non-numeric", "never"])
+            .help("When to quote fields (default: necessary, or never with --raw)"))
+        .arg(Arg::with_name("QUOTE")
+            .long("quote")
+            .takes_value(true)
+            .default_value("\"")
+            .help("Character used to quote fields"))
+        .arg(Arg::with_name("TERMINATOR")
+            .long("terminator")
+            .takes_value(true)
+            .default_value("lf")
+            .possible_values(&["lf", "crlf"])
+            .help("Record terminator to use (default: lf; pass crlf for Excel-style output)"))
+        .arg(Arg::with_name("ESCAPE")
+            .long("escape")
+            .takes_value(true)
+            .help("Escape character for quotes, instead of doubling them"))
+        .arg(Arg::with_name("FLATTEN")
+            .long("flatten")
+            .help("Recursively flatten nested objects/arrays into one column per leaf value"))
+        .arg(Arg::with_name("FLATTEN-ARRAYS")
+            .long("flatten-arrays")
+            .takes_value(true)
+            .default_value("positional")
+            .possible_values(&["positional", "join"])
+            .help("With --flatten, expand arrays by index or join scalar arrays into one cell"))
+        .arg(Arg::with_name("FLATTEN-JOIN-SEP")
+            .long("flatten-join-sep")
+            .takes_value(true)
+            .default_value(",")
+            .help("Separator used to join a scalar array's values with --flatten-arrays=join"))
+        .arg(Arg::with_name("NULL")
+            .long("null")
+            .takes_value(true)
+            .default_value("")
+            .help("String to render for a JSON null value"))
+        .arg(Arg::with_name("TRUE")
+            .long("true")
+            .takes_value(true)
+            .default_value("true")
+            .help("String to render for a JSON true value"))
+        .arg(Arg::with_name("FALSE")
+            .long("false")
+            .takes_value(true)
+            .default_value("false")
+            .help("String to render for a JSON false value"))
+        .arg(Arg::with_name("FLOAT-FMT")
+            .long("float-fmt")
+            .takes_value(true)
+            .value_name("PRECISION")
+            .help("Decimal places to round floating-point numbers to; integers are always printed as-is (default: print as-is)"))
+        .arg(Arg::with_name("RDR-BUF")
+            .long("rdr-buf")
+            .takes_value(true)
+            .default_value("16384")
+            .help("Read buffer size in bytes for file or stdin input"))
+        .arg(Arg::with_name("WTR-BUF")
+            .long("wtr-buf")
+            .takes_value(true)
+            .default_value("65536")
+            .help("Write buffer size in bytes for file/piped output"))
+        .arg(Arg::with_name("REVERSE")
+            .long("reverse")
+            .alias("from-csv")
+            .help("Read delimited input and emit JSON instead"))
+        .arg(Arg::with_name("JSONL")
+            .long("jsonl")
+            .requires("REVERSE")
+            .help("With --reverse, emit one JSON record per line instead of a JSON array"))
+        .arg(Arg::with_name("TYPES")
+            .long("types")
+            .requires("REVERSE")
+            .help("With --reverse, parse numeric/boolean/empty cells into JSON types instead of strings"))
         .get_matches();
 
-    run(JCArgs::from_matches(&matches))
+    if matches.is_present("REVERSE") {
+        run_reverse(&matches)
+    } else {
+        run(JCArgs::from_matches(&matches)?)
+    }
+}
+
+fn run_reverse(matches: &ArgMatches) -> Result<()> {
+    let io_args = JCArgs::from_matches(matches)?;
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    let in_stream = io_args.input_or(&stdin)?;
+    let out_stream = io_args.output_or(&stdout)?;
+    let decode_args = decode::DecodeArgs {
+        separator: io_args.separator,
+        quote: io_args.quote,
+        escape: io_args.escape,
+        has_headers: io_args.show_headers,
+        jsonl: matches.is_present("JSONL"),
+        types: matches.is_present("TYPES"),
+    };
+    decode::run(decode_args, in_stream, out_stream)
 }
@@ -0,0 +1,95 @@
+use crate::Result;
+use regex::Regex;
+use std::collections::HashSet;
+
+fn index_to_name(index: usize, universe: &[String]) -> Result<String> {
+    if index == 0 {
+        return Err(format_err!("column index must be 1-based, got 0"));
+    }
+    universe.get(index - 1).cloned()
+        .ok_or_else(|| format_err!("column index {} out of range (1..={})", index, universe.len()))
+}
+
+fn is_digits(s: &str) -> bool {
+    !s.is_empty() && s.chars().all(|c| c.is_ascii_digit())
+}
+
+fn parse_index(token: &str) -> Result<usize> {
+    token.parse().map_err(|_| format_err!("column index {:?} is out of range", token))
+}
+
+fn is_range_token(token: &str) -> bool {
+    match token.find('-') {
+        Some(dash) => {
+            let (start, end) = (&token[..dash], &token[dash + 1..]);
+            is_digits(start) && (end.is_empty() || is_digits(end))
+        }
+        None => false,
+    }
+}
+
+fn is_regex_token(token: &str) -> bool {
+    token.len() >= 2 && token.starts_with('/') && token.ends_with('/')
+}
+
+/// Whether resolving `columns` requires the concrete key universe: true if
+/// any token is an index, a range, a `/regex/`, or a negation (which falls
+/// back to "the whole universe minus these"). A selection made up purely of
+/// literal names/paths needs no universe and can be resolved per-record.
+pub fn needs_universe(columns: &[String]) -> bool {
+    columns.iter().any(|raw| {
+        let token = raw.strip_prefix('!').unwrap_or(raw);
+        raw.starts_with('!') || is_digits(token) || is_range_token(token) || is_regex_token(token)
+    })
+}
+
+/// Resolves a single selector token against `universe`: a 1-based index, an
+/// inclusive (possibly open-ended or reversed) index range, a `/regex/`
+/// matched against column names, or otherwise a literal column name/path
+/// passed through unchanged.
+fn resolve_token(token: &str, universe: &[String]) -> Result<Vec<String>> {
+    if let Some(pattern) = token.strip_prefix('/').and_then(|rest| rest.strip_suffix('/')) {
+        let re = Regex::new(pattern).map_err(|e| format_err!("invalid column regex {:?}: {}", pattern, e))?;
+        return Ok(universe.iter().filter(|c| re.is_match(c)).cloned().collect());
+    }
+    if is_digits(token) {
+        return Ok(vec![index_to_name(parse_index(token)?, universe)?]);
+    }
+    if let Some(dash) = token.find('-') {
+        let (start_str, end_str) = (&token[..dash], &token[dash + 1..]);
+        if is_digits(start_str) && (end_str.is_empty() || is_digits(end_str)) {
+            let start = parse_index(start_str)?;
+            let end = if end_str.is_empty() { universe.len() } else { parse_index(end_str)? };
+            let indices: Vec<usize> = if start <= end { (start..=end).collect() } else { (end..=start).rev().collect() };
+            return indices.into_iter().map(|i| index_to_name(i, universe)).collect();
+        }
+    }
+    Ok(vec![token.to_string()])
+}
+
+/// Resolves an xsv-style column selection (`1,3,5`, `2-5`, `3-`, reverse
+/// ranges, `/regex/` name matching, and `!`-prefixed negation) against the
+/// concrete `universe` of available columns, into an ordered, deduplicated
+/// list of column paths. A selection made up entirely of negated tokens
+/// excludes those columns from the full universe instead.
+pub fn resolve_selection(columns: &[String], universe: &[String]) -> Result<Vec<String>> {
+    let mut positive = Vec::new();
+    let mut negative = HashSet::new();
+    let mut saw_positive = false;
+    for raw in columns {
+        let (negate, token) = match raw.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, raw.as_str()),
+        };
+        let resolved = resolve_token(token, universe)?;
+        if negate {
+            negative.extend(resolved);
+        } else {
+            saw_positive = true;
+            positive.extend(resolved);
+        }
+    }
+    let base = if saw_positive { positive } else { universe.to_vec() };
+    let mut seen = HashSet::new();
+    Ok(base.into_iter().filter(|c| !negative.contains(c) && seen.insert(c.clone())).collect())
+}